@@ -8,7 +8,7 @@ use regex::Regex;
 use tokio::sync::Mutex;
 use std::sync::atomic::{AtomicBool};
 use std::{vec};
-use tauri::{command, CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu, SystemTraySubmenu, Window};
+use tauri::{command, CustomMenuItem, GlobalShortcutManager, Manager, RunEvent, SystemTray, SystemTrayEvent, SystemTrayMenu, SystemTraySubmenu, Window};
 use window_vibrancy::{apply_blur, apply_vibrancy, NSVisualEffectMaterial};
 use window_shadows::set_shadow;
 use tauri_plugin_positioner::{WindowExt, Position};
@@ -19,8 +19,9 @@ mod upload;
 mod utils;
 mod media;
 
-use recording::{RecordingState, start_dual_recording, stop_all_recordings};
-use media::{enumerate_audio_devices};
+use recording::{RecordingState, DisplaysState, MicMuteState, start_dual_recording, stop_all_recordings, set_mic_muted, toggle_mic_muted, start_interval_screenshots, stop_interval_screenshots, create_timelapse_gif, get_active_displays, get_system_info, copy_system_info_to_clipboard, start_display_watcher, query_displays, stop_recording_before_exit};
+use upload::{UploadQuotaCache, SpacesCache, check_upload_quota, list_spaces};
+use media::{enumerate_audio_devices, start_audio_device_watcher};
 use utils::{has_screen_capture_access};
 
 use ffmpeg_sidecar::{
@@ -33,6 +34,8 @@ use ffmpeg_sidecar::{
 
 use winit::monitor::{MonitorHandle, VideoMode};
 
+/// Toggles the microphone mute while recording, from anywhere.
+const MIC_MUTE_SHORTCUT: &str = "CmdOrControl+Shift+M";
 
 fn main() {    
     let _ = fix_path_env::fix();
@@ -221,6 +224,32 @@ fn main() {
             };
 
             app.manage(Arc::new(Mutex::new(recording_state)));
+            app.manage(Arc::new(Mutex::new(MicMuteState { mic_muted: None })));
+            let displays = match app.get_window("main").map(|window| query_displays(&window)) {
+                Some(Ok(displays)) => displays,
+                Some(Err(e)) => {
+                    eprintln!("Failed to query displays: {}", e);
+                    Vec::new()
+                }
+                None => Vec::new(),
+            };
+            app.manage(Arc::new(Mutex::new(DisplaysState { displays })));
+            start_display_watcher(handle.clone());
+            start_audio_device_watcher(handle.clone());
+            app.manage(Arc::new(Mutex::new(UploadQuotaCache::default())));
+            app.manage(Arc::new(Mutex::new(SpacesCache::default())));
+
+            let shortcut_handle = handle.clone();
+            if let Err(e) = app.global_shortcut_manager().register(MIC_MUTE_SHORTCUT, move || {
+                let app = shortcut_handle.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = toggle_mic_muted(app).await {
+                        println!("Ignoring microphone mute shortcut: {}", e);
+                    }
+                });
+            }) {
+                eprintln!("Failed to register microphone mute shortcut: {}", e);
+            }
 
             let tray_handle = app.tray_handle();
             app.listen_global("toggle-recording", move |event| {
@@ -303,6 +332,15 @@ fn main() {
         .invoke_handler(tauri::generate_handler![
             start_dual_recording,
             stop_all_recordings,
+            set_mic_muted,
+            start_interval_screenshots,
+            stop_interval_screenshots,
+            create_timelapse_gif,
+            get_active_displays,
+            get_system_info,
+            copy_system_info_to_clipboard,
+            check_upload_quota,
+            list_spaces,
             enumerate_audio_devices,
             start_server,
             open_screen_capture_preferences,
@@ -328,7 +366,11 @@ fn main() {
                     }
                 }
                 "quit" => {
-                    app.exit(0);
+                    let app = app.clone();
+                    tauri::async_runtime::spawn(async move {
+                        stop_recording_before_exit(&app).await;
+                        app.exit(0);
+                    });
                 }
                 item_id => {
                     if !item_id.starts_with("in") {
@@ -359,6 +401,22 @@ fn main() {
             },
             _ => {}
         })
-        .run(tauri::generate_context!())
-        .expect("Error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("Error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::ExitRequested { api, .. } = event {
+                let state = app.state::<Arc<Mutex<RecordingState>>>();
+                // A held lock means a recording is being started or stopped, so treat it as active.
+                let recording_active = state.try_lock().map(|guard| guard.media_process.is_some()).unwrap_or(true);
+
+                if recording_active {
+                    api.prevent_exit();
+                    let app = app.clone();
+                    tauri::async_runtime::spawn(async move {
+                        stop_recording_before_exit(&app).await;
+                        app.exit(0);
+                    });
+                }
+            }
+        });
 }
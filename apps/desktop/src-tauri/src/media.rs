@@ -2,7 +2,7 @@ use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::SampleFormat;
 use std::process::{Stdio};
 use byteorder::{ByteOrder, LittleEndian};
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use std::sync::{Arc, atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering}};
 use std::io::{ErrorKind::WouldBlock, Error};
 use std::time::{Instant, Duration};
 use std::path::Path;
@@ -14,12 +14,40 @@ use tokio::process::{Command, Child, ChildStdin};
 use tokio::sync::{mpsc, Mutex};
 use tokio::try_join;
 
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
 use crate::recording::RecordingOptions;
 use crate::utils::{ffmpeg_path_as_str};
 use crate::upload::upload_file;
 use capture::{Capturer, Display};
 
 const FRAME_RATE: u64 = 30;
+pub const MIN_AUDIO_LEVEL_DB: f32 = -96.0;
+const MAX_INPUT_LATENCY_MS: i32 = 500;
+
+const BLACK_FRAME_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const BLACK_FRAME_GRID_SIZE: usize = 32;
+const BLACK_PIXEL_MAX_VALUE: u8 = 16;
+const BLACK_FRAME_RATIO: f32 = 0.99;
+// Long enough that fades, dark slides and loading screens don't trip the warning.
+const PROTECTED_CONTENT_MIN_DURATION: Duration = Duration::from_secs(10);
+
+const AUDIO_DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const AUDIO_DEVICE_DEBOUNCE: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ProtectedContentPayload {
+    start_time: f64,
+    end_time: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct AudioDevicesChangedPayload {
+    devices: Vec<String>,
+}
 
 pub struct MediaRecorder {
     pub options: Option<RecordingOptions>,
@@ -34,6 +62,9 @@ pub struct MediaRecorder {
     video_channel_sender: Option<mpsc::Sender<Vec<u8>>>,
     video_channel_receiver: Option<mpsc::Receiver<Vec<u8>>>,
     should_stop: Arc<AtomicBool>,
+    screenshot_interval_secs: Arc<AtomicU64>,
+    mic_muted: Arc<AtomicBool>,
+    audio_level_db: Arc<AtomicU32>,
     start_time: Option<Instant>,
     audio_file_path: Option<String>,
     video_file_path: Option<String>,
@@ -55,13 +86,16 @@ impl MediaRecorder {
             video_channel_sender: None,
             video_channel_receiver: None,
             should_stop: Arc::new(AtomicBool::new(false)),
+            screenshot_interval_secs: Arc::new(AtomicU64::new(0)),
+            mic_muted: Arc::new(AtomicBool::new(false)),
+            audio_level_db: Arc::new(AtomicU32::new(MIN_AUDIO_LEVEL_DB.to_bits())),
             start_time: None,
             audio_file_path: None,
             video_file_path: None,
         }
     }
 
-    pub async fn start_media_recording(&mut self, options: RecordingOptions, audio_file_path: &str, video_file_path: &str, screenshot_file_path: &str, custom_device: Option<&str>, max_screen_width: usize, max_screen_height: usize) -> Result<(), String> {
+    pub async fn start_media_recording(&mut self, app: AppHandle, options: RecordingOptions, audio_file_path: &str, video_file_path: &str, screenshot_file_path: &str, custom_device: Option<&str>, max_screen_width: usize, max_screen_height: usize) -> Result<(), String> {
         self.options = Some(options.clone());
 
         println!("Custom device: {:?}", custom_device);
@@ -103,6 +137,7 @@ impl MediaRecorder {
         let video_channel_receiver = Arc::new(Mutex::new(self.video_channel_receiver.take()));
 
         let should_stop = Arc::clone(&self.should_stop);
+        let screenshot_interval_secs = Arc::clone(&self.screenshot_interval_secs);
         
         let mut input_devices = devices.filter_map(|device| {
             let supported_input_configs = device.supported_input_configs();
@@ -169,10 +204,19 @@ impl MediaRecorder {
                   &config.into(),
                   {
                       let audio_start_time = Arc::clone(&audio_start_time);
+                      let mic_muted = Arc::clone(&self.mic_muted);
+                      let audio_level = Arc::clone(&self.audio_level_db);
                       move |data: &[i8], _: &_| {
                           let mut first_frame_time_guard = audio_start_time.try_lock();
                           
-                          let bytes = data.iter().map(|&sample| sample as u8).collect::<Vec<u8>>();
+                          let mut bytes = data.iter().map(|&sample| sample as u8).collect::<Vec<u8>>();
+                          let level_db = if mic_muted.load(Ordering::Relaxed) {
+                              bytes.fill(0);
+                              MIN_AUDIO_LEVEL_DB
+                          } else {
+                              calculate_level_db(data.iter().map(|&sample| sample as f32 / i8::MAX as f32))
+                          };
+                          audio_level.store(level_db.to_bits(), Ordering::Relaxed);
                           if let Some(sender) = &audio_channel_sender {
                             if sender.try_send(bytes).is_err() {
                               eprintln!("Channel send error. Dropping data.");
@@ -195,11 +239,20 @@ impl MediaRecorder {
                   &config.into(),
                   {
                       let audio_start_time = Arc::clone(&audio_start_time); 
+                      let mic_muted = Arc::clone(&self.mic_muted);
+                      let audio_level = Arc::clone(&self.audio_level_db);
                       move |data: &[i16], _: &_| {
                           let mut first_frame_time_guard = audio_start_time.try_lock();
 
                           let mut bytes = vec![0; data.len() * 2];
                           LittleEndian::write_i16_into(data, &mut bytes);
+                          let level_db = if mic_muted.load(Ordering::Relaxed) {
+                              bytes.fill(0);
+                              MIN_AUDIO_LEVEL_DB
+                          } else {
+                              calculate_level_db(data.iter().map(|&sample| sample as f32 / i16::MAX as f32))
+                          };
+                          audio_level.store(level_db.to_bits(), Ordering::Relaxed);
                           if let Some(sender) = &audio_channel_sender {
                               if sender.try_send(bytes).is_err() {
                                   eprintln!("Channel send error. Dropping data.");
@@ -222,11 +275,20 @@ impl MediaRecorder {
                   &config.into(),
                   {
                       let audio_start_time = Arc::clone(&audio_start_time);
+                      let mic_muted = Arc::clone(&self.mic_muted);
+                      let audio_level = Arc::clone(&self.audio_level_db);
                       move |data: &[i32], _: &_| {
                           let mut first_frame_time_guard = audio_start_time.try_lock();
 
                           let mut bytes = vec![0; data.len() * 2];
                           LittleEndian::write_i32_into(data, &mut bytes);
+                          let level_db = if mic_muted.load(Ordering::Relaxed) {
+                              bytes.fill(0);
+                              MIN_AUDIO_LEVEL_DB
+                          } else {
+                              calculate_level_db(data.iter().map(|&sample| sample as f32 / i32::MAX as f32))
+                          };
+                          audio_level.store(level_db.to_bits(), Ordering::Relaxed);
                           if let Some(sender) = &audio_channel_sender {
                               if sender.try_send(bytes).is_err() {
                                   eprintln!("Channel send error. Dropping data.");
@@ -249,11 +311,20 @@ impl MediaRecorder {
                   &config.into(),
                   {
                       let audio_start_time = Arc::clone(&audio_start_time);
+                      let mic_muted = Arc::clone(&self.mic_muted);
+                      let audio_level = Arc::clone(&self.audio_level_db);
                       move |data: &[f32], _: &_| {
                           let mut first_frame_time_guard = audio_start_time.try_lock();
 
                           let mut bytes = vec![0; data.len() * 4];
                           LittleEndian::write_f32_into(data, &mut bytes);
+                          let level_db = if mic_muted.load(Ordering::Relaxed) {
+                              bytes.fill(0);
+                              MIN_AUDIO_LEVEL_DB
+                          } else {
+                              calculate_level_db(data.iter().copied())
+                          };
+                          audio_level.store(level_db.to_bits(), Ordering::Relaxed);
                           if let Some(sender) = &audio_channel_sender {
                               if sender.try_send(bytes).is_err() {
                                   eprintln!("Channel send error. Dropping data.");
@@ -283,6 +354,9 @@ impl MediaRecorder {
         let video_start_time_clone = Arc::clone(&video_start_time); 
         let screenshot_file_path_owned = format!("{}/screen-capture.jpg", screenshot_file_path);
         let capture_frame_at = Duration::from_secs(3);
+        let timelapse_dir = format!("{}/timelapse", screenshot_file_path);
+        std::fs::create_dir_all(&timelapse_dir).map_err(|e| format!("Failed to create timelapse directory: {}", e))?;
+        let input_latency_ms = options.input_latency_ms.unwrap_or(0);
         
         std::thread::spawn(move || {
             println!("Starting video recording capture thread...");
@@ -301,6 +375,9 @@ impl MediaRecorder {
             let start_time = Instant::now();
             let mut time_next = Instant::now() + spf;
             let mut screenshot_captured: bool = false;
+            let mut last_interval_screenshot: Option<Instant> = None;
+            let mut interval_screenshot_count = 0u32;
+            let mut black_frame_detector = BlackFrameDetector::new();
             
             while !should_stop.load(Ordering::SeqCst) {
                 let options_clone = options.clone();
@@ -324,24 +401,12 @@ impl MediaRecorder {
                             if now - start_time >= capture_frame_at && !screenshot_captured {
                                 screenshot_captured = true;
                                 let screenshot_file_path_owned_cloned = screenshot_file_path_owned.clone();
-                                let mut frame_data_clone = frame_data.clone();
+                                let frame_data_clone = frame_data.clone();
 
                                 std::thread::spawn(move || {
-                                    for chunk in frame_data_clone.chunks_mut(4) {
-                                        chunk.swap(0, 2);
-                                    }
-
                                     let path = Path::new(&screenshot_file_path_owned_cloned);
-                                    let image: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::from_raw(
-                                        adjusted_width.try_into().unwrap(),
-                                        adjusted_height.try_into().unwrap(),
-                                        frame_data_clone
-                                    ).expect("Failed to create image buffer");
 
-                                    let mut output_file = std::fs::File::create(&path).expect("Failed to create output file");
-                                    let mut encoder = JpegEncoder::new_with_quality(&mut output_file, 20);
-
-                                    if let Err(e) = encoder.encode_image(&image) {
+                                    if let Err(e) = save_frame_as_jpeg(frame_data_clone, adjusted_width, adjusted_height, path, 20) {
                                         eprintln!("Failed to save screenshot: {}", e);
                                     } else {
                                         if !is_local_mode {
@@ -365,6 +430,36 @@ impl MediaRecorder {
                                 });
                             }
 
+                            if let Some((suspected, black_since)) = black_frame_detector.update(&frame_data, adjusted_width, adjusted_height, now) {
+                                let payload = ProtectedContentPayload {
+                                    start_time: black_since.saturating_duration_since(start_time).as_secs_f64(),
+                                    end_time: if suspected { None } else { Some((now - start_time).as_secs_f64()) },
+                                };
+                                let event = if suspected { "protected-content-suspected" } else { "protected-content-cleared" };
+
+                                println!("Black frames detected from {:.1}s, ended at {:?}", payload.start_time, payload.end_time);
+
+                                if let Err(e) = app.emit_all(event, payload) {
+                                    eprintln!("Failed to emit protected content event: {}", e);
+                                }
+                            }
+
+                            let interval_secs = screenshot_interval_secs.load(Ordering::SeqCst);
+                            if interval_secs == 0 {
+                                last_interval_screenshot = None;
+                            } else if last_interval_screenshot.map_or(true, |last| now - last >= Duration::from_secs(interval_secs)) {
+                                last_interval_screenshot = Some(now);
+                                let interval_screenshot_path = format!("{}/frame_{:05}.jpg", timelapse_dir, interval_screenshot_count);
+                                interval_screenshot_count += 1;
+                                let frame_data_clone = frame_data.clone();
+
+                                std::thread::spawn(move || {
+                                    if let Err(e) = save_frame_as_jpeg(frame_data_clone, adjusted_width, adjusted_height, Path::new(&interval_screenshot_path), 60) {
+                                        eprintln!("Failed to save interval screenshot: {}", e);
+                                    }
+                                });
+                            }
+
                             if let Some(sender) = &video_channel_sender {
                                 if sender.try_send(frame_data).is_err() {
                                     eprintln!("Channel send error. Dropping data.");
@@ -466,9 +561,11 @@ impl MediaRecorder {
 
         if custom_device != Some("None") {
             println!("Adjusting FFmpeg commands based on start times...");
+            println!("Requested audio input latency compensation: {}ms", input_latency_ms);
             adjust_ffmpeg_commands_based_on_start_times(
                 Arc::clone(&audio_start_time),
                 Arc::clone(&video_start_time),
+                input_latency_ms,
                 &mut ffmpeg_audio_command,
                 &mut ffmpeg_video_command,
             ).await;
@@ -546,6 +643,20 @@ impl MediaRecorder {
         Ok(())
     }
 
+    /// Level of the most recent microphone buffer, in dBFS, as written to the recording.
+    pub fn audio_level(&self) -> Arc<AtomicU32> {
+        Arc::clone(&self.audio_level_db)
+    }
+
+    /// Flag that replaces captured microphone samples with silence while set, so the audio track keeps its length.
+    pub fn mic_muted(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.mic_muted)
+    }
+
+    pub fn set_screenshot_interval(&self, interval_secs: u32) {
+        self.screenshot_interval_secs.store(interval_secs as u64, Ordering::SeqCst);
+    }
+
     pub fn trigger_play (&mut self) -> Result<(), &'static str> {
         if let Some(ref mut stream) = self.stream {
             stream.play().map_err(|_| "Failed to play stream")?;
@@ -670,13 +781,122 @@ impl MediaRecorder {
 
 }
 
+/// Flags sustained runs of near-black frames, which is what protected (DRM) content
+/// looks like to screen capture, by sampling a sparse pixel grid once per second.
+struct BlackFrameDetector {
+    last_sample: Option<Instant>,
+    black_since: Option<Instant>,
+    suspected: bool,
+}
+
+impl BlackFrameDetector {
+    fn new() -> Self {
+        BlackFrameDetector {
+            last_sample: None,
+            black_since: None,
+            suspected: false,
+        }
+    }
+
+    /// Returns `(true, start)` when a black run becomes long enough to be suspicious and
+    /// `(false, start)` when visible content returns after one, where `start` is when the run began.
+    fn update(&mut self, frame: &[u8], width: usize, height: usize, now: Instant) -> Option<(bool, Instant)> {
+        if self.last_sample.map_or(false, |last| now - last < BLACK_FRAME_SAMPLE_INTERVAL) {
+            return None;
+        }
+        self.last_sample = Some(now);
+
+        if !is_black_frame(frame, width, height) {
+            let black_since = self.black_since.take();
+            if self.suspected {
+                self.suspected = false;
+                return black_since.map(|since| (false, since));
+            }
+            return None;
+        }
+
+        if self.suspected {
+            return None;
+        }
+
+        let black_since = *self.black_since.get_or_insert(now);
+        if now - black_since >= PROTECTED_CONTENT_MIN_DURATION {
+            self.suspected = true;
+            return Some((true, black_since));
+        }
+
+        None
+    }
+}
+
+fn is_black_frame(frame: &[u8], width: usize, height: usize) -> bool {
+    if width == 0 || height == 0 {
+        return false;
+    }
+
+    let mut sampled = 0usize;
+    let mut black = 0usize;
+
+    for grid_y in 0..BLACK_FRAME_GRID_SIZE {
+        let y = (grid_y * 2 + 1) * height / (BLACK_FRAME_GRID_SIZE * 2);
+        for grid_x in 0..BLACK_FRAME_GRID_SIZE {
+            let x = (grid_x * 2 + 1) * width / (BLACK_FRAME_GRID_SIZE * 2);
+            let offset = (y * width + x) * 4;
+
+            if let Some(pixel) = frame.get(offset..offset + 3) {
+                sampled += 1;
+                if pixel.iter().all(|&channel| channel <= BLACK_PIXEL_MAX_VALUE) {
+                    black += 1;
+                }
+            }
+        }
+    }
+
+    sampled > 0 && black as f32 / sampled as f32 > BLACK_FRAME_RATIO
+}
+
+fn calculate_level_db(samples: impl Iterator<Item = f32>) -> f32 {
+    let (sum_squares, count) = samples.fold((0.0f32, 0usize), |(sum, count), sample| (sum + sample * sample, count + 1));
+
+    if count == 0 {
+        return MIN_AUDIO_LEVEL_DB;
+    }
+
+    let rms = (sum_squares / count as f32).sqrt();
+    (20.0 * rms.log10()).max(MIN_AUDIO_LEVEL_DB)
+}
+
+fn save_frame_as_jpeg(mut frame_data: Vec<u8>, width: usize, height: usize, path: &Path, quality: u8) -> Result<(), String> {
+    // Frames are captured as BGRA, so swap the red and blue channels before encoding.
+    for chunk in frame_data.chunks_mut(4) {
+        chunk.swap(0, 2);
+    }
+
+    let image: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::from_raw(
+        width.try_into().unwrap(),
+        height.try_into().unwrap(),
+        frame_data
+    ).ok_or("Failed to create image buffer")?;
+
+    let mut output_file = std::fs::File::create(path).map_err(|e| format!("Failed to create output file: {}", e))?;
+    let mut encoder = JpegEncoder::new_with_quality(&mut output_file, quality);
+
+    encoder.encode_image(&image).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn enumerate_audio_devices() -> Vec<String> {
+    list_audio_input_devices().unwrap_or_else(|e| {
+        eprintln!("Failed to enumerate audio devices: {}", e);
+        Vec::new()
+    })
+}
+
+fn list_audio_input_devices() -> Result<Vec<String>, String> {
     let host = cpal::default_host();
-    let default_device = host.default_input_device().expect("No default input device available");
-    let default_device_name = default_device.name().expect("Failed to get default device name");
+    let default_device_name = host.default_input_device().and_then(|device| device.name().ok());
 
-    let devices = host.devices().expect("Failed to get devices");
+    let devices = host.devices().map_err(|e| format!("Failed to get devices: {}", e))?;
     let mut input_device_names: Vec<String> = devices
         .filter_map(|device| {
             let supported_input_configs = device.supported_input_configs();
@@ -688,10 +908,91 @@ pub fn enumerate_audio_devices() -> Vec<String> {
         })
         .collect();
 
-    input_device_names.retain(|name| name != &default_device_name);
-    input_device_names.insert(0, default_device_name);
+    if let Some(default_device_name) = default_device_name {
+        input_device_names.retain(|name| name != &default_device_name);
+        input_device_names.insert(0, default_device_name);
+    }
+
+    Ok(input_device_names)
+}
+
+/// Names of the current input devices, sorted so the list can be compared between polls.
+/// Unlike `list_audio_input_devices` this doesn't open each device to query its configs.
+fn audio_input_device_names() -> Result<Vec<String>, String> {
+    let mut names: Vec<String> = cpal::default_host()
+        .input_devices()
+        .map_err(|e| format!("Failed to get input devices: {}", e))?
+        .filter_map(|device| device.name().ok())
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Reports a new device list only once it has stayed the same for `AUDIO_DEVICE_DEBOUNCE`,
+/// since Bluetooth headsets tend to flap while reconnecting.
+struct AudioDeviceDebouncer {
+    last_emitted: Vec<String>,
+    pending: Option<(Vec<String>, Instant)>,
+}
+
+impl AudioDeviceDebouncer {
+    fn new(devices: Vec<String>) -> Self {
+        AudioDeviceDebouncer {
+            last_emitted: devices,
+            pending: None,
+        }
+    }
+
+    /// Returns the device list when a change has settled.
+    fn update(&mut self, devices: Vec<String>, now: Instant) -> Option<Vec<String>> {
+        self.pending = match self.pending.take() {
+            Some((pending_devices, since)) if pending_devices == devices => Some((pending_devices, since)),
+            _ if devices == self.last_emitted => None,
+            _ => Some((devices, now)),
+        };
+
+        match &self.pending {
+            Some((devices, since)) if now.duration_since(*since) >= AUDIO_DEVICE_DEBOUNCE => {
+                self.last_emitted = devices.clone();
+                self.pending = None;
+                Some(self.last_emitted.clone())
+            }
+            _ => None,
+        }
+    }
+}
+
+pub fn start_audio_device_watcher(app: AppHandle) {
+    std::thread::spawn(move || {
+        let mut debouncer = AudioDeviceDebouncer::new(audio_input_device_names().unwrap_or_default());
+
+        loop {
+            std::thread::sleep(AUDIO_DEVICE_POLL_INTERVAL);
+
+            let names = match audio_input_device_names() {
+                Ok(names) => names,
+                Err(e) => {
+                    eprintln!("Failed to poll audio devices: {}", e);
+                    continue;
+                }
+            };
 
-    input_device_names
+            if debouncer.update(names, Instant::now()).is_none() {
+                continue;
+            }
+
+            // Only the settled change pays for the full enumeration the UI expects.
+            match list_audio_input_devices() {
+                Ok(devices) => {
+                    println!("Audio input devices changed: {:?}", devices);
+                    if let Err(e) = app.emit_all("audio-devices-changed", AudioDevicesChangedPayload { devices }) {
+                        eprintln!("Failed to emit audio-devices-changed event: {}", e);
+                    }
+                }
+                Err(e) => eprintln!("Failed to enumerate audio devices: {}", e),
+            }
+        }
+    });
 }
 
 use tokio::io::{BufReader, AsyncBufReadExt};
@@ -740,37 +1041,252 @@ async fn wait_for_start_times(
 async fn adjust_ffmpeg_commands_based_on_start_times(
     audio_start_time: Arc<Mutex<Option<Instant>>>,
     video_start_time: Arc<Mutex<Option<Instant>>>,
+    input_latency_ms: i32,
     ffmpeg_audio_command: &mut Vec<String>,
     ffmpeg_video_command: &mut Vec<String>,
 ) {
     let (audio_start, video_start) = wait_for_start_times(audio_start_time, video_start_time).await;
-    let duration_difference = if audio_start > video_start {
-        audio_start.duration_since(video_start)
-    } else {
-        video_start.duration_since(audio_start)
-    };
 
-    println!("Duration difference: {:?}", duration_difference);
     println!("Audio start: {:?}", audio_start);
     println!("Video start: {:?}", video_start);
 
-    // Convert the duration difference to a float representing seconds
-    let offset_seconds = duration_difference.as_secs() as f64 
-        + duration_difference.subsec_nanos() as f64 * 1e-9;
+    match start_time_offset(audio_start, video_start, input_latency_ms) {
+        Some((OffsetTarget::Video, offset)) => {
+            // Offset the video start time
+            ffmpeg_video_command.splice(0..0, vec![
+                "-itsoffset".to_string(), format!("{:.3}", offset.as_secs_f64())
+            ]);
+            println!("Applying -itsoffset {:.3} to video", offset.as_secs_f64());
+        }
+        Some((OffsetTarget::Audio, offset)) => {
+            // Offset the audio start time
+            ffmpeg_audio_command.splice(0..0, vec![
+                "-itsoffset".to_string(), format!("{:.3}", offset.as_secs_f64())
+            ]);
+            println!("Applying -itsoffset {:.3} to audio", offset.as_secs_f64());
+        }
+        None => {}
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum OffsetTarget {
+    Audio,
+    Video,
+}
+
+/// Works out which ffmpeg input needs an `-itsoffset`, and by how much, given when each
+/// stream started. `input_latency_ms` is clamped to +/-500ms and shifts the audio start
+/// earlier, since samples from a device with input latency describe sound from that long
+/// before they reached the callback.
+fn start_time_offset(audio_start: Instant, video_start: Instant, input_latency_ms: i32) -> Option<(OffsetTarget, Duration)> {
+    let input_latency_ms = input_latency_ms.clamp(-MAX_INPUT_LATENCY_MS, MAX_INPUT_LATENCY_MS);
+    let input_latency = Duration::from_millis(input_latency_ms.unsigned_abs() as u64);
+    let audio_start = if input_latency_ms >= 0 {
+        audio_start.checked_sub(input_latency).unwrap_or(audio_start)
+    } else {
+        audio_start + input_latency
+    };
 
-    // Depending on which started first, adjust the relevant FFmpeg command
     if audio_start > video_start {
-        // Offset the video start time
-        ffmpeg_video_command.splice(0..0, vec![
-            "-itsoffset".to_string(), format!("{:.3}", offset_seconds)
-        ]);
-        println!("Applying -itsoffset {:.3} to video", offset_seconds);
+        Some((OffsetTarget::Video, audio_start - video_start))
     } else if video_start > audio_start {
-        // Offset the audio start time
-        ffmpeg_audio_command.splice(0..0, vec![
-            "-itsoffset".to_string(), format!("{:.3}", offset_seconds)
-        ]);
-        println!("Applying -itsoffset {:.3} to audio", offset_seconds);
+        Some((OffsetTarget::Audio, video_start - audio_start))
+    } else {
+        None
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid_frame(width: usize, height: usize, value: u8) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(width * height * 4);
+        for _ in 0..width * height {
+            frame.extend_from_slice(&[value, value, value, 255]);
+        }
+        frame
     }
 
-}
\ No newline at end of file
+    fn at(start: Instant, secs: u64) -> Instant {
+        start + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn short_black_run_does_not_fire() {
+        let black = solid_frame(64, 64, 0);
+        let content = solid_frame(64, 64, 200);
+        let start = Instant::now();
+        let mut detector = BlackFrameDetector::new();
+
+        for secs in 0..10 {
+            assert_eq!(detector.update(&black, 64, 64, at(start, secs)), None);
+        }
+        assert_eq!(detector.update(&content, 64, 64, at(start, 10)), None);
+        // The run was reset, so another short run doesn't add up with the previous one
+        for secs in 11..20 {
+            assert_eq!(detector.update(&black, 64, 64, at(start, secs)), None);
+        }
+    }
+
+    #[test]
+    fn sustained_black_run_fires_once_with_start_time() {
+        let black = solid_frame(64, 64, 0);
+        let start = Instant::now();
+        let mut detector = BlackFrameDetector::new();
+
+        let events: Vec<_> = (0..20)
+            .filter_map(|secs| detector.update(&black, 64, 64, at(start, secs)))
+            .collect();
+
+        assert_eq!(events, vec![(true, start)]);
+    }
+
+    #[test]
+    fn clears_when_content_returns() {
+        let black = solid_frame(64, 64, 0);
+        let content = solid_frame(64, 64, 200);
+        let start = Instant::now();
+        let mut detector = BlackFrameDetector::new();
+
+        for secs in 0..12 {
+            detector.update(&black, 64, 64, at(start, secs));
+        }
+        assert_eq!(detector.update(&content, 64, 64, at(start, 12)), Some((false, start)));
+        assert_eq!(detector.update(&content, 64, 64, at(start, 13)), None);
+
+        // A later run is measured from its own start
+        for secs in 14..24 {
+            assert_eq!(detector.update(&black, 64, 64, at(start, secs)), None);
+        }
+        assert_eq!(detector.update(&black, 64, 64, at(start, 24)), Some((true, at(start, 14))));
+    }
+
+    #[test]
+    fn samples_at_most_once_per_interval() {
+        let black = solid_frame(64, 64, 0);
+        let content = solid_frame(64, 64, 200);
+        let start = Instant::now();
+        let mut detector = BlackFrameDetector::new();
+
+        for secs in 0..11 {
+            detector.update(&black, 64, 64, at(start, secs));
+        }
+        // Content arriving inside the sampling interval is ignored
+        assert_eq!(detector.update(&content, 64, 64, at(start, 10) + Duration::from_millis(500)), None);
+        assert_eq!(detector.update(&content, 64, 64, at(start, 11)), Some((false, start)));
+    }
+
+    fn devices(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn audio_device_change_is_reported_once_stable() {
+        let start = Instant::now();
+        let mut debouncer = AudioDeviceDebouncer::new(devices(&["Built-in"]));
+
+        assert_eq!(debouncer.update(devices(&["Built-in"]), at(start, 1)), None);
+        assert_eq!(debouncer.update(devices(&["AirPods", "Built-in"]), at(start, 2)), None);
+        assert_eq!(debouncer.update(devices(&["AirPods", "Built-in"]), at(start, 3)), None);
+        assert_eq!(debouncer.update(devices(&["AirPods", "Built-in"]), at(start, 4)), Some(devices(&["AirPods", "Built-in"])));
+        assert_eq!(debouncer.update(devices(&["AirPods", "Built-in"]), at(start, 5)), None);
+    }
+
+    #[test]
+    fn flapping_audio_devices_restart_the_debounce() {
+        let start = Instant::now();
+        let mut debouncer = AudioDeviceDebouncer::new(devices(&["Built-in"]));
+
+        assert_eq!(debouncer.update(devices(&["AirPods", "Built-in"]), at(start, 1)), None);
+        assert_eq!(debouncer.update(devices(&["Built-in"]), at(start, 2)), None);
+        assert_eq!(debouncer.update(devices(&["AirPods", "Built-in"]), at(start, 3)), None);
+        assert_eq!(debouncer.update(devices(&["Built-in"]), at(start, 4)), None);
+        // Back to the reported list, so nothing is pending any more
+        assert_eq!(debouncer.update(devices(&["Built-in"]), at(start, 10)), None);
+
+        assert_eq!(debouncer.update(devices(&["AirPods", "Built-in"]), at(start, 11)), None);
+        assert_eq!(debouncer.update(devices(&["AirPods", "Built-in"]), at(start, 13)), Some(devices(&["AirPods", "Built-in"])));
+    }
+
+    #[test]
+    fn removed_audio_device_is_reported() {
+        let start = Instant::now();
+        let mut debouncer = AudioDeviceDebouncer::new(devices(&["AirPods", "Built-in"]));
+
+        assert_eq!(debouncer.update(devices(&["Built-in"]), at(start, 1)), None);
+        assert_eq!(debouncer.update(devices(&["Built-in"]), at(start, 3)), Some(devices(&["Built-in"])));
+    }
+
+    #[test]
+    fn offsets_the_stream_that_started_first() {
+        let video_start = Instant::now() + Duration::from_secs(1);
+        let audio_start = video_start + Duration::from_millis(200);
+
+        assert_eq!(start_time_offset(audio_start, video_start, 0), Some((OffsetTarget::Video, Duration::from_millis(200))));
+        assert_eq!(start_time_offset(video_start, audio_start, 0), Some((OffsetTarget::Audio, Duration::from_millis(200))));
+        assert_eq!(start_time_offset(video_start, video_start, 0), None);
+    }
+
+    #[test]
+    fn positive_latency_moves_audio_earlier() {
+        let video_start = Instant::now() + Duration::from_secs(1);
+        let audio_start = video_start + Duration::from_millis(200);
+
+        assert_eq!(start_time_offset(audio_start, video_start, 150), Some((OffsetTarget::Video, Duration::from_millis(50))));
+        assert_eq!(start_time_offset(audio_start, video_start, 200), None);
+        assert_eq!(start_time_offset(audio_start, video_start, 300), Some((OffsetTarget::Audio, Duration::from_millis(100))));
+    }
+
+    #[test]
+    fn negative_latency_moves_audio_later() {
+        let video_start = Instant::now() + Duration::from_secs(1);
+
+        assert_eq!(start_time_offset(video_start, video_start, -120), Some((OffsetTarget::Video, Duration::from_millis(120))));
+    }
+
+    #[test]
+    fn latency_is_clamped() {
+        let video_start = Instant::now() + Duration::from_secs(1);
+
+        assert_eq!(start_time_offset(video_start, video_start, 2000), Some((OffsetTarget::Audio, Duration::from_millis(500))));
+        assert_eq!(start_time_offset(video_start, video_start, -2000), Some((OffsetTarget::Video, Duration::from_millis(500))));
+    }
+
+    #[test]
+    fn dark_frame_counts_as_black() {
+        assert!(is_black_frame(&solid_frame(64, 64, BLACK_PIXEL_MAX_VALUE), 64, 64));
+        assert!(!is_black_frame(&solid_frame(64, 64, BLACK_PIXEL_MAX_VALUE + 1), 64, 64));
+    }
+
+    #[test]
+    fn dark_frame_with_bright_area_is_not_black() {
+        let (width, height) = (64, 64);
+        let mut frame = solid_frame(width, height, 8);
+        // A small bright block (e.g. a cursor or notification) covers 16 of the 1024 samples
+        for y in 0..8 {
+            for x in 0..8 {
+                let offset = (y * width + x) * 4;
+                frame[offset..offset + 3].copy_from_slice(&[255, 255, 255]);
+            }
+        }
+
+        assert!(!is_black_frame(&frame, width, height));
+    }
+
+    #[test]
+    fn empty_dimensions_are_not_black() {
+        assert!(!is_black_frame(&[], 0, 0));
+        assert!(!is_black_frame(&solid_frame(64, 64, 0), 0, 64));
+        assert!(!is_black_frame(&solid_frame(64, 64, 0), 64, 0));
+    }
+
+    #[test]
+    fn truncated_buffer_only_uses_available_pixels() {
+        let black = solid_frame(64, 64, 0);
+
+        assert!(!is_black_frame(&[], 64, 64));
+        assert!(is_black_frame(&black[..black.len() / 2], 64, 64));
+        assert!(!is_black_frame(&black[..2], 64, 64));
+    }
+}
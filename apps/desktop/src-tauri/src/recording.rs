@@ -2,17 +2,18 @@ use std::path::{Path, PathBuf};
 use std::collections::HashSet;
 use std::io::{self, BufReader, BufRead, ErrorKind};
 use std::fs::File;
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use std::sync::{Arc, atomic::{AtomicBool, AtomicU32, Ordering}};
 use tokio::sync:: {Mutex};
 use tokio::task::JoinHandle;
-use tokio::time::{Duration};
+use tokio::time::{Duration, Instant};
 use serde::{Serialize, Deserialize};
-use tauri::State;
+use tauri::{AppHandle, ClipboardManager, Manager, State, Window};
 use futures::future::join_all;
 
 use crate::upload::{upload_file};
+use crate::utils::{ffmpeg_path_as_str};
 
-use crate::media::MediaRecorder;
+use crate::media::{MediaRecorder, MIN_AUDIO_LEVEL_DB};
 
 pub struct RecordingState {
   pub media_process: Option<MediaRecorder>,
@@ -39,11 +40,75 @@ pub struct RecordingOptions {
   pub audio_name: String,
   pub aws_region: String,
   pub aws_bucket: String,
+  pub input_latency_ms: Option<i32>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DisplayInfo {
+  pub id: String,
+  pub name: String,
+  pub width: u32,
+  pub height: u32,
+  pub x: i32,
+  pub y: i32,
+  pub scale_factor: f64,
+  pub is_primary: bool,
+}
+
+pub struct DisplaysState {
+  pub displays: Vec<DisplayInfo>,
+}
+
+/// The active recording's microphone mute flag, kept apart from `RecordingState` so muting
+/// doesn't wait behind `stop_recording`, which holds that lock while uploads finish.
+pub struct MicMuteState {
+  pub mic_muted: Option<Arc<AtomicBool>>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct DisplaysChangedPayload {
+  added: Vec<DisplayInfo>,
+  removed: Vec<String>,
+  changed: Vec<DisplayInfo>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemInfo {
+  pub os_name: String,
+  pub os_version: String,
+  pub cpu_model: String,
+  pub cpu_cores: usize,
+  pub total_memory_bytes: u64,
+  pub displays: Vec<DisplayInfo>,
+  pub cap_version: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct AudioLevelChangePayload {
+  segment_time: f64,
+  level_db: f32,
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct MicMuteChangedPayload {
+  muted: bool,
+}
+
+const AUDIO_LEVEL_INTERVAL: Duration = Duration::from_millis(100);
+
+const EXIT_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+const DISPLAY_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
 #[tauri::command]
 pub async fn start_dual_recording(
+  app: AppHandle,
   state: State<'_, Arc<Mutex<RecordingState>>>,
+  mic_mute_state: State<'_, Arc<Mutex<MicMuteState>>>,
   options: RecordingOptions,
 ) -> Result<(), String> {
   println!("Starting screen recording...");
@@ -70,9 +135,14 @@ pub async fn start_dual_recording(
     Some(options.audio_name.clone())
   };
   
-  let media_recording_preparation = prepare_media_recording(&options, &audio_chunks_dir, &video_chunks_dir, &screenshot_dir, audio_name, state_guard.max_screen_width, state_guard.max_screen_height);
+  let media_recording_preparation = prepare_media_recording(app.clone(), &options, &audio_chunks_dir, &video_chunks_dir, &screenshot_dir, audio_name, state_guard.max_screen_width, state_guard.max_screen_height);
   let media_recording_result = media_recording_preparation.await.map_err(|e| e.to_string())?;
 
+  if options.audio_name != "None" {
+      start_audio_level_events(app, media_recording_result.audio_level(), shutdown_flag.clone());
+  }
+
+  mic_mute_state.lock().await.mic_muted = Some(media_recording_result.mic_muted());
   state_guard.media_process = Some(media_recording_result);
   state_guard.recording_options = Some(options.clone());
   state_guard.shutdown_flag = shutdown_flag.clone();
@@ -108,16 +178,21 @@ pub async fn start_dual_recording(
 }
 
 #[tauri::command]
-pub async fn stop_all_recordings(state: State<'_, Arc<Mutex<RecordingState>>>) -> Result<(), String> {
+pub async fn stop_all_recordings(state: State<'_, Arc<Mutex<RecordingState>>>, mic_mute_state: State<'_, Arc<Mutex<MicMuteState>>>) -> Result<(), String> {
+    stop_recording(&state, &mic_mute_state).await
+}
+
+async fn stop_recording(state: &Mutex<RecordingState>, mic_mute_state: &Mutex<MicMuteState>) -> Result<(), String> {
     let mut guard = state.lock().await;
     
     println!("Stopping media recording...");
     
     guard.shutdown_flag.store(true, Ordering::SeqCst);
+    mic_mute_state.lock().await.mic_muted = None;
 
     if let Some(mut media_process) = guard.media_process.take() {
         println!("Stopping media recording...");
-        media_process.stop_media_recording().await.expect("Failed to stop media recording");
+        media_process.stop_media_recording().await.map_err(|e| format!("Failed to stop media recording: {}", e))?;
     }
 
     let is_local_mode = match dotenv_codegen::dotenv!("NEXT_PUBLIC_LOCAL_MODE") {
@@ -138,6 +213,142 @@ pub async fn stop_all_recordings(state: State<'_, Arc<Mutex<RecordingState>>>) -
     Ok(())
 }
 
+/// Finalizes an in-progress recording before the app quits so its last segments are
+/// written and uploaded rather than cut off. Everything, including waiting for the state
+/// lock held by a stop that is already underway, gives up after `EXIT_STOP_TIMEOUT`.
+pub async fn stop_recording_before_exit(app: &AppHandle) {
+    let state = app.state::<Arc<Mutex<RecordingState>>>().inner().clone();
+    let mic_mute_state = app.state::<Arc<Mutex<MicMuteState>>>().inner().clone();
+
+    let stop = async {
+        if state.lock().await.media_process.is_none() {
+            return Ok(false);
+        }
+
+        println!("Recording in progress, stopping it before exit...");
+        stop_recording(&state, &mic_mute_state).await.map(|_| true)
+    };
+
+    match tokio::time::timeout(EXIT_STOP_TIMEOUT, stop).await {
+        Ok(Ok(true)) => println!("Recording stopped before exit."),
+        Ok(Ok(false)) => {},
+        Ok(Err(e)) => eprintln!("Failed to stop recording before exit: {}", e),
+        Err(_) => eprintln!("Timed out stopping recording before exit."),
+    }
+}
+
+#[tauri::command]
+pub async fn set_mic_muted(app: AppHandle, state: State<'_, Arc<Mutex<MicMuteState>>>, muted: bool) -> Result<(), String> {
+    update_mic_muted(&app, &state, |_| muted).await
+}
+
+/// Flips the microphone mute of the active recording, for the global mute shortcut.
+pub async fn toggle_mic_muted(app: AppHandle) -> Result<(), String> {
+    let state = app.state::<Arc<Mutex<MicMuteState>>>().inner().clone();
+    update_mic_muted(&app, &state, |muted| !muted).await
+}
+
+async fn update_mic_muted(app: &AppHandle, state: &Mutex<MicMuteState>, update: impl FnOnce(bool) -> bool) -> Result<(), String> {
+    let guard = state.lock().await;
+    let mic_muted = guard.mic_muted.as_ref().ok_or("No recording in progress".to_string())?;
+    let muted = update(mic_muted.load(Ordering::SeqCst));
+    mic_muted.store(muted, Ordering::SeqCst);
+    drop(guard);
+
+    println!("Microphone {}", if muted { "muted" } else { "unmuted" });
+
+    app.emit_all("mic-mute-changed", MicMuteChangedPayload { muted }).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn start_interval_screenshots(state: State<'_, Arc<Mutex<RecordingState>>>, interval_secs: u32) -> Result<(), String> {
+    if interval_secs == 0 {
+        return Err("Screenshot interval must be at least one second".to_string());
+    }
+
+    let guard = state.lock().await;
+    let media_process = guard.media_process.as_ref().ok_or("No recording in progress".to_string())?;
+    media_process.set_screenshot_interval(interval_secs);
+
+    println!("Capturing a screenshot every {} seconds", interval_secs);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn stop_interval_screenshots(state: State<'_, Arc<Mutex<RecordingState>>>) -> Result<(), String> {
+    let guard = state.lock().await;
+    if let Some(media_process) = guard.media_process.as_ref() {
+        media_process.set_screenshot_interval(0);
+    }
+
+    Ok(())
+}
+
+/// Builds a GIF from the latest recording's interval screenshots. The GIF is written to
+/// `timelapses/` since `screenshots/` is wiped when the next recording starts.
+#[tauri::command]
+pub async fn create_timelapse_gif(state: State<'_, Arc<Mutex<RecordingState>>>) -> Result<PathBuf, String> {
+    let guard = state.lock().await;
+    let data_dir = guard.data_dir.clone()
+        .ok_or("Data directory is not set in the recording state".to_string())?;
+    let video_id = guard.recording_options.as_ref()
+        .map(|options| options.video_id.clone())
+        .ok_or("No recording to create a timelapse from".to_string())?;
+    drop(guard);
+
+    let timelapse_dir = data_dir.join("screenshots/timelapse");
+    let frame_pattern = timelapse_dir.join("frame_%05d.jpg");
+    let output_dir = data_dir.join("timelapses");
+    let output_path = output_dir.join(format!("{}.gif", video_id));
+
+    if !timelapse_dir.join("frame_00000.jpg").is_file() {
+        return Err("No interval screenshots were captured in the latest recording".to_string());
+    }
+
+    std::fs::create_dir_all(&output_dir).map_err(|e| format!("Failed to create timelapses directory: {}", e))?;
+
+    let ffmpeg_binary_path_str = ffmpeg_path_as_str()?;
+    let output = tokio::process::Command::new(ffmpeg_binary_path_str)
+        .args(["-y", "-framerate", "5", "-i"])
+        .arg(&frame_pattern)
+        .args(["-vf", "scale=960:-1:flags=lanczos,split[a][b];[a]palettegen[p];[b][p]paletteuse"])
+        .arg(&output_path)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run FFmpeg: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!("FFmpeg failed to create timelapse: {}", String::from_utf8_lossy(&output.stderr)));
+    }
+
+    println!("Timelapse saved to {:?}", output_path);
+
+    Ok(output_path)
+}
+
+/// Emits `audio-level-change` every `AUDIO_LEVEL_INTERVAL` with the microphone level being recorded,
+/// until the recording's shutdown flag is set.
+fn start_audio_level_events(app: AppHandle, audio_level: Arc<AtomicU32>, shutdown_flag: Arc<AtomicBool>) {
+    tokio::spawn(async move {
+        let start_time = Instant::now();
+
+        while !shutdown_flag.load(Ordering::SeqCst) {
+            let level_db = f32::from_bits(audio_level.load(Ordering::Relaxed)).max(MIN_AUDIO_LEVEL_DB);
+            let payload = AudioLevelChangePayload {
+                segment_time: start_time.elapsed().as_secs_f64(),
+                level_db,
+            };
+
+            if let Err(e) = app.emit_all("audio-level-change", payload) {
+                eprintln!("Failed to emit audio level event: {}", e);
+            }
+
+            tokio::time::sleep(AUDIO_LEVEL_INTERVAL).await;
+        }
+    });
+}
+
 fn clean_and_create_dir(dir: &Path) -> Result<(), String> {
     if dir.exists() {
         // Instead of just reading the directory, this will also handle subdirectories.
@@ -226,6 +437,7 @@ fn load_segment_list(segment_list_path: &Path) -> io::Result<HashSet<String>> {
 }
 
 async fn prepare_media_recording(
+  app: AppHandle,
   options: &RecordingOptions,
   audio_chunks_dir: &Path,
   screenshot_dir: &Path,
@@ -238,6 +450,213 @@ async fn prepare_media_recording(
   let audio_file_path = audio_chunks_dir.to_str().unwrap();
   let video_file_path = video_chunks_dir.to_str().unwrap();
   let screenshot_dir_path = screenshot_dir.to_str().unwrap();
-  media_recorder.start_media_recording(options.clone(), audio_file_path, screenshot_dir_path, video_file_path, audio_name.as_ref().map(String::as_str), max_screen_width, max_screen_height).await?;
+  media_recorder.start_media_recording(app, options.clone(), audio_file_path, screenshot_dir_path, video_file_path, audio_name.as_ref().map(String::as_str), max_screen_width, max_screen_height).await?;
   Ok(media_recorder)
-}
\ No newline at end of file
+}
+
+#[tauri::command]
+pub async fn get_system_info(app: AppHandle, displays_state: State<'_, Arc<Mutex<DisplaysState>>>) -> Result<SystemInfo, String> {
+    let mut system = sysinfo::System::new();
+    system.refresh_cpu();
+    system.refresh_memory();
+
+    let cpu_model = system.cpus().first()
+        .map(|cpu| cpu.brand().trim().to_string())
+        .unwrap_or_else(|| "Unknown".to_string());
+    let cpu_cores = system.physical_core_count().unwrap_or_else(|| system.cpus().len());
+
+    let displays = displays_state.lock().await.displays.clone();
+
+    Ok(SystemInfo {
+        os_name: sysinfo::System::name().unwrap_or_else(|| std::env::consts::OS.to_string()),
+        os_version: sysinfo::System::os_version().unwrap_or_else(|| "Unknown".to_string()),
+        cpu_model,
+        cpu_cores,
+        total_memory_bytes: system.total_memory(),
+        displays,
+        cap_version: app.package_info().version.to_string(),
+    })
+}
+
+#[tauri::command]
+pub async fn copy_system_info_to_clipboard(app: AppHandle, displays_state: State<'_, Arc<Mutex<DisplaysState>>>) -> Result<(), String> {
+    let info = get_system_info(app.clone(), displays_state).await?;
+
+    let displays = if info.displays.is_empty() {
+        "Unknown".to_string()
+    } else {
+        info.displays.iter()
+            .map(|display| format!(
+                "{}x{} @{}x{}",
+                display.width,
+                display.height,
+                display.scale_factor,
+                if display.is_primary { " (primary)" } else { "" }
+            ))
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+
+    let markdown = format!(
+        "### System Info\n\
+        - **Cap version:** {}\n\
+        - **OS:** {} {}\n\
+        - **CPU:** {} ({} cores)\n\
+        - **RAM:** {:.1} GB\n\
+        - **Displays:** {}\n",
+        info.cap_version,
+        info.os_name,
+        info.os_version,
+        info.cpu_model,
+        info.cpu_cores,
+        info.total_memory_bytes as f64 / 1024.0 / 1024.0 / 1024.0,
+        displays,
+    );
+
+    // Writing to the clipboard waits on the main event loop, so keep it off the async runtime.
+    tauri::async_runtime::spawn_blocking(move || app.clipboard_manager().write_text(markdown))
+        .await
+        .map_err(|e| format!("Failed to run clipboard task: {}", e))?
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_active_displays(state: State<'_, Arc<Mutex<DisplaysState>>>) -> Result<Vec<DisplayInfo>, String> {
+    let guard = state.lock().await;
+    Ok(guard.displays.clone())
+}
+
+pub fn query_displays(window: &Window) -> Result<Vec<DisplayInfo>, String> {
+    let primary = window.primary_monitor().map_err(|e| e.to_string())?;
+    let primary_position = primary.as_ref().map(|monitor| *monitor.position());
+
+    let monitors = window.available_monitors().map_err(|e| e.to_string())?;
+    let displays = monitors.iter().map(|monitor| {
+        let size = monitor.size();
+        let position = monitor.position();
+        let name = monitor.name().cloned().unwrap_or_default();
+        // Monitors have no stable identifier in Tauri and identical models share a name,
+        // so the position is part of the id.
+        let id = format!("{}@{},{}", name, position.x, position.y);
+
+        DisplayInfo {
+            id,
+            name,
+            width: size.width,
+            height: size.height,
+            x: position.x,
+            y: position.y,
+            scale_factor: monitor.scale_factor(),
+            is_primary: primary_position == Some(*position),
+        }
+    }).collect();
+
+    Ok(displays)
+}
+
+fn diff_displays(previous: &[DisplayInfo], current: &[DisplayInfo]) -> DisplaysChangedPayload {
+    let added = current.iter()
+        .filter(|display| !previous.iter().any(|old| old.id == display.id))
+        .cloned()
+        .collect();
+    let removed = previous.iter()
+        .filter(|display| !current.iter().any(|new| new.id == display.id))
+        .map(|display| display.id.clone())
+        .collect();
+    let changed = current.iter()
+        .filter(|display| previous.iter().any(|old| old.id == display.id && old != *display))
+        .cloned()
+        .collect();
+
+    DisplaysChangedPayload { added, removed, changed }
+}
+
+/// Keeps `DisplaysState` in sync with the connected monitors and emits `displays-changed`
+/// whenever one is plugged in, unplugged or reconfigured. The state should already hold
+/// the displays found at startup, so only later changes are reported.
+///
+/// Tauri does not surface display reconfiguration events, so the monitor list is re-queried
+/// on a short interval instead.
+pub fn start_display_watcher(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            if let Some(window) = app.get_window("main") {
+                // Monitor queries wait on the main event loop, so keep them off the async workers.
+                let displays = tauri::async_runtime::spawn_blocking(move || query_displays(&window))
+                    .await
+                    .unwrap_or_else(|e| Err(format!("Failed to run display query: {}", e)));
+
+                match displays {
+                    Ok(displays) => {
+                        let state = app.state::<Arc<Mutex<DisplaysState>>>();
+                        let mut guard = state.lock().await;
+
+                        let payload = diff_displays(&guard.displays, &displays);
+
+                        if !payload.added.is_empty() || !payload.removed.is_empty() || !payload.changed.is_empty() {
+                            println!(
+                                "Displays changed: {} added, {} removed, {} changed",
+                                payload.added.len(),
+                                payload.removed.len(),
+                                payload.changed.len()
+                            );
+                            guard.displays = displays;
+                            drop(guard);
+
+                            if let Err(e) = app.emit_all("displays-changed", payload) {
+                                eprintln!("Failed to emit displays changed event: {}", e);
+                            }
+                        }
+                    },
+                    Err(e) => eprintln!("Failed to query displays: {}", e),
+                }
+            }
+
+            tokio::time::sleep(DISPLAY_POLL_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(id: &str, width: u32) -> DisplayInfo {
+        DisplayInfo {
+            id: id.to_string(),
+            name: "Display".to_string(),
+            width,
+            height: 1080,
+            x: 0,
+            y: 0,
+            scale_factor: 1.0,
+            is_primary: false,
+        }
+    }
+
+    #[test]
+    fn diff_displays_reports_added_and_removed_by_id() {
+        let payload = diff_displays(&[display("a", 1920)], &[display("b", 1920)]);
+
+        assert_eq!(payload.added, vec![display("b", 1920)]);
+        assert_eq!(payload.removed, vec!["a".to_string()]);
+        assert!(payload.changed.is_empty());
+    }
+
+    #[test]
+    fn diff_displays_reports_reconfigured_display_as_changed() {
+        let payload = diff_displays(&[display("a", 1920)], &[display("a", 2560)]);
+
+        assert!(payload.added.is_empty());
+        assert!(payload.removed.is_empty());
+        assert_eq!(payload.changed, vec![display("a", 2560)]);
+    }
+
+    #[test]
+    fn diff_displays_ignores_unchanged_displays() {
+        let displays = [display("a", 1920), display("b", 1920)];
+        let payload = diff_displays(&displays, &displays);
+
+        assert!(payload.added.is_empty() && payload.removed.is_empty() && payload.changed.is_empty());
+    }
+}
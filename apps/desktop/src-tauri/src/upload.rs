@@ -5,134 +5,109 @@ use std::path::Path;
 use std::process::{Command, Output};
 use std::str;
 use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use regex::Regex;
+use serde::{Serialize, Deserialize};
 use serde_json::Value as JsonValue;
+use tauri::State;
+use tokio::sync::Mutex;
 
 use crate::recording::RecordingOptions;
 use crate::utils::{ffmpeg_path_as_str};
 
-pub async fn upload_file(
-    options: Option<RecordingOptions>,
-    file_path: String,
-    file_type: String,
-) -> Result<String, String> {
-    if let Some(ref options) = options {
-        println!("Uploading video...");
+const UPLOAD_QUOTA_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+const SPACES_CACHE_TTL: Duration = Duration::from_secs(60);
 
-        let duration = get_video_duration(&file_path).map_err(|e| format!("Failed to get video duration: {}", e))?;
-        let duration_str = duration.to_string();
-
-        let file_name = Path::new(&file_path)
-            .file_name()
-            .and_then(|name| name.to_str())
-            .ok_or("Invalid file path")?
-            .to_string();
-
-        let file_key = format!("{}/{}/{}/{}", options.user_id, options.video_id, file_type, file_name);
-
-        let server_url_base: &'static str = dotenv_codegen::dotenv!("NEXT_PUBLIC_URL");
-        let server_url = format!("{}/api/upload/signed", server_url_base);
-        
-        let body: serde_json::Value;
-
-        if file_type == "video" {
-            let (codec_name, width, height, frame_rate, bit_rate) = log_video_info(&file_path).map_err(|e| format!("Failed to log video info: {}", e))?;
-              
-            body = serde_json::json!({
-                "userId": options.user_id,
-                "fileKey": file_key,
-                "awsBucket": options.aws_bucket,
-                "awsRegion": options.aws_region,
-                "duration": duration_str,
-                "resolution": format!("{}x{}", width, height),
-                "framerate": frame_rate,
-                "bandwidth": bit_rate,
-                "videoCodec": codec_name,
-            });
-        } else {
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct UploadQuota {
+    pub used_bytes: u64,
+    pub total_bytes: Option<u64>,
+    pub plan: String,
+}
 
-            body = serde_json::json!({
-                "userId": options.user_id,
-                "fileKey": file_key,
-                "awsBucket": options.aws_bucket,
-                "awsRegion": options.aws_region,
-                "duration": duration_str,
-            });
-        }
+/// Remembers the last response fetched with a given session token for `ttl`.
+pub struct TimedCache<T> {
+    ttl: Duration,
+    entry: Option<(String, Instant, T)>,
+}
+
+impl<T: Clone> TimedCache<T> {
+    pub fn new(ttl: Duration) -> Self {
+        TimedCache { ttl, entry: None }
+    }
 
-        let client = reqwest::Client::new();
-        let server_response = client.post(server_url)
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("Failed to send request to Next.js handler: {}", e))?
-            .text()
-            .await
-            .map_err(|e| format!("Failed to read response from Next.js handler: {}", e))?;
-
-        println!("Server response: {}", server_response);
-
-
-        // Deserialize the server response
-        let presigned_post_data: JsonValue = serde_json::from_str(&server_response)
-            .map_err(|e| format!("Failed to deserialize server response: {}", e))?;
-
-        // Construct the multipart form for the file upload
-        let fields = presigned_post_data["presignedPostData"]["fields"].as_object()
-            .ok_or("Fields object is missing or not an object")?;
-        
-        let mut form = reqwest::multipart::Form::new();
-        
-        for (key, value) in fields.iter() {
-            let value_str = value.as_str()
-                .ok_or(format!("Value for key '{}' is not a string", key))?;
-            form = form.text(key.to_string(), value_str.to_owned());
+    fn get(&self, token: &str, now: Instant) -> Option<T> {
+        match &self.entry {
+            Some((cached_token, fetched_at, value)) if cached_token == token && now.duration_since(*fetched_at) < self.ttl => {
+                Some(value.clone())
+            }
+            _ => None,
         }
+    }
 
-        println!("Uploading file: {}", file_path);
-        
-        let mime_type = if file_path.to_lowercase().ends_with(".aac") {
-            "audio/aac"
-        } else if file_path.to_lowercase().ends_with(".webm") { 
-            "audio/webm" 
-        } else if file_path.to_lowercase().ends_with(".mp3") { 
-            "audio/mpeg"
-        } else {
-            "video/mp2t"
-        };
+    fn insert(&mut self, token: String, value: T, now: Instant) {
+        self.entry = Some((token, now, value));
+    }
+}
 
-        let file_bytes = tokio::fs::read(&file_path).await.map_err(|e| format!("Failed to read file: {}", e))?;
-        let file_part = reqwest::multipart::Part::bytes(file_bytes)
-            .file_name(file_name.clone())
-            .mime_str(mime_type)
-            .map_err(|e| format!("Error setting MIME type: {}", e))?;
+pub type UploadQuotaCache = TimedCache<UploadQuota>;
+pub type SpacesCache = TimedCache<SpacesList>;
 
-        form = form.part("file", file_part);
+impl Default for UploadQuotaCache {
+    fn default() -> Self {
+        TimedCache::new(UPLOAD_QUOTA_CACHE_TTL)
+    }
+}
 
-        let post_url = presigned_post_data["presignedPostData"]["url"].as_str()
-            .ok_or("URL is missing or not a string")?;
+impl Default for SpacesCache {
+    fn default() -> Self {
+        TimedCache::new(SPACES_CACHE_TTL)
+    }
+}
 
-        println!("Uploading file to: {}", post_url);
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Space {
+    pub id: String,
+    pub name: String,
+}
 
-        let response = client.post(post_url)
-            .multipart(form)
-            .send()
-            .await;
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SpacesList {
+    pub spaces: Vec<Space>,
+    /// False when the server predates the spaces endpoint, so the UI can hide the picker.
+    pub supported: bool,
+}
 
-        match response {
-            Ok(response) if response.status().is_success() => {
-                println!("File uploaded successfully");
-            }
-            Ok(response) => {
-                let status = response.status();
-                let error_body = response.text().await.unwrap_or_else(|_| "<no response body>".to_string());
-                eprintln!("Failed to upload file. Status: {}. Body: {}", status, error_body);
-                return Err(format!("Failed to upload file. Status: {}. Body: {}", status, error_body));
-            }
-            Err(e) => {
-                return Err(format!("Failed to send upload file request: {}", e));
+pub async fn upload_file(
+    options: Option<RecordingOptions>,
+    file_path: String,
+    file_type: String,
+) -> Result<String, String> {
+    if let Some(ref options) = options {
+        println!("Uploading video...");
+
+        let original_file_path = file_path;
+        let file_path = if file_type == "screenshot" {
+            let screenshot_path = original_file_path.clone();
+            tokio::task::spawn_blocking(move || prepare_screenshot_for_upload(&screenshot_path))
+                .await
+                .map_err(|e| format!("Failed to prepare screenshot: {}", e))??
+        } else {
+            original_file_path.clone()
+        };
+
+        let upload_result = upload_prepared_file(options, &file_path, &file_type).await;
+        if upload_result.is_err() && file_path != original_file_path {
+            // Don't leave the transcoded JPEG behind in the temp directory when the upload fails
+            if let Err(e) = tokio::fs::remove_file(&file_path).await {
+                println!("Failed to remove transcoded screenshot: {}", e);
             }
         }
+        let file_key = upload_result?;
 
         println!("Removing file after upload: {}", file_path);
         let remove_result = tokio::fs::remove_file(&file_path).await;
@@ -142,12 +117,272 @@ pub async fn upload_file(
         }
         remove_result.map_err(|e| format!("Failed to remove file after upload: {}", e))?;
 
+        if file_path != original_file_path {
+            if let Err(e) = tokio::fs::remove_file(&original_file_path).await {
+                println!("Failed to remove original file after upload: {}", e);
+            }
+        }
+
         Ok(file_key)
     } else {
         return Err("No recording options provided".to_string());
     }
 }
 
+/// Requests a presigned post for the prepared file and uploads it, returning the file key.
+async fn upload_prepared_file(options: &RecordingOptions, file_path: &str, file_type: &str) -> Result<String, String> {
+    let duration = get_video_duration(file_path).map_err(|e| format!("Failed to get video duration: {}", e))?;
+    let duration_str = duration.to_string();
+
+    let file_name = Path::new(file_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or("Invalid file path")?
+        .to_string();
+
+    let file_key = format!("{}/{}/{}/{}", options.user_id, options.video_id, file_type, file_name);
+
+    let server_url_base: &'static str = dotenv_codegen::dotenv!("NEXT_PUBLIC_URL");
+    let server_url = format!("{}/api/upload/signed", server_url_base);
+    
+    let body: serde_json::Value;
+
+    if file_type == "video" {
+        let (codec_name, width, height, frame_rate, bit_rate) = log_video_info(file_path).map_err(|e| format!("Failed to log video info: {}", e))?;
+          
+        body = serde_json::json!({
+            "userId": options.user_id,
+            "fileKey": file_key,
+            "awsBucket": options.aws_bucket,
+            "awsRegion": options.aws_region,
+            "duration": duration_str,
+            "resolution": format!("{}x{}", width, height),
+            "framerate": frame_rate,
+            "bandwidth": bit_rate,
+            "videoCodec": codec_name,
+        });
+    } else {
+
+        body = serde_json::json!({
+            "userId": options.user_id,
+            "fileKey": file_key,
+            "awsBucket": options.aws_bucket,
+            "awsRegion": options.aws_region,
+            "duration": duration_str,
+        });
+    }
+
+    let client = reqwest::Client::new();
+    let server_response = client.post(server_url)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send request to Next.js handler: {}", e))?
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read response from Next.js handler: {}", e))?;
+
+    println!("Server response: {}", server_response);
+
+
+    // Deserialize the server response
+    let presigned_post_data: JsonValue = serde_json::from_str(&server_response)
+        .map_err(|e| format!("Failed to deserialize server response: {}", e))?;
+
+    // Construct the multipart form for the file upload
+    let fields = presigned_post_data["presignedPostData"]["fields"].as_object()
+        .ok_or("Fields object is missing or not an object")?;
+    
+    let mut form = reqwest::multipart::Form::new();
+    
+    for (key, value) in fields.iter() {
+        let value_str = value.as_str()
+            .ok_or(format!("Value for key '{}' is not a string", key))?;
+        form = form.text(key.to_string(), value_str.to_owned());
+    }
+
+    println!("Uploading file: {}", file_path);
+    
+    let mime_type = if file_type == "screenshot" {
+        "image/jpeg"
+    } else if file_path.to_lowercase().ends_with(".aac") {
+        "audio/aac"
+    } else if file_path.to_lowercase().ends_with(".webm") { 
+        "audio/webm" 
+    } else if file_path.to_lowercase().ends_with(".mp3") { 
+        "audio/mpeg"
+    } else {
+        "video/mp2t"
+    };
+
+    let file_bytes = tokio::fs::read(file_path).await.map_err(|e| format!("Failed to read file: {}", e))?;
+    let file_part = reqwest::multipart::Part::bytes(file_bytes)
+        .file_name(file_name.clone())
+        .mime_str(mime_type)
+        .map_err(|e| format!("Error setting MIME type: {}", e))?;
+
+    form = form.part("file", file_part);
+
+    let post_url = presigned_post_data["presignedPostData"]["url"].as_str()
+        .ok_or("URL is missing or not a string")?;
+
+    println!("Uploading file to: {}", post_url);
+
+    let response = client.post(post_url)
+        .multipart(form)
+        .send()
+        .await;
+
+    match response {
+        Ok(response) if response.status().is_success() => {
+            println!("File uploaded successfully");
+        }
+        Ok(response) => {
+            let status = response.status();
+            let error_body = response.text().await.unwrap_or_else(|_| "<no response body>".to_string());
+            eprintln!("Failed to upload file. Status: {}. Body: {}", status, error_body);
+            return Err(format!("Failed to upload file. Status: {}. Body: {}", status, error_body));
+        }
+        Err(e) => {
+            return Err(format!("Failed to send upload file request: {}", e));
+        }
+    }
+
+    Ok(file_key)
+}
+
+#[tauri::command]
+pub async fn check_upload_quota(
+    state: State<'_, Arc<Mutex<UploadQuotaCache>>>,
+    token: String,
+) -> Result<UploadQuota, String> {
+    // Held for the whole request so concurrent callers share one fetch instead of each hitting the API.
+    let mut cache = state.lock().await;
+
+    if let Some(quota) = cache.get(&token, Instant::now()) {
+        return Ok(quota);
+    }
+
+    let server_url_base: &'static str = dotenv_codegen::dotenv!("NEXT_PUBLIC_URL");
+    let server_url = format!("{}/api/desktop/quota", server_url_base);
+
+    let response = reqwest::Client::new()
+        .get(server_url)
+        .bearer_auth(&token)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send upload quota request: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("Failed to fetch upload quota. Status: {}", response.status()));
+    }
+
+    let quota: UploadQuota = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to deserialize upload quota response: {}", e))?;
+
+    cache.insert(token, quota.clone(), Instant::now());
+
+    Ok(quota)
+}
+
+#[tauri::command]
+pub async fn list_spaces(
+    state: State<'_, Arc<Mutex<SpacesCache>>>,
+    token: String,
+) -> Result<SpacesList, String> {
+    let mut cache = state.lock().await;
+
+    if let Some(spaces) = cache.get(&token, Instant::now()) {
+        return Ok(spaces);
+    }
+
+    let server_url_base: &'static str = dotenv_codegen::dotenv!("NEXT_PUBLIC_URL");
+    let server_url = format!("{}/api/desktop/spaces", server_url_base);
+
+    let response = reqwest::Client::new()
+        .get(server_url)
+        .bearer_auth(&token)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to send spaces request: {}", e))?;
+
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read spaces response: {}", e))?;
+    let spaces = parse_spaces_response(status, &body)?;
+
+    if !spaces.supported {
+        println!("Server does not support spaces, uploading without one");
+    }
+
+    cache.insert(token, spaces.clone(), Instant::now());
+
+    Ok(spaces)
+}
+
+/// Servers that predate spaces answer with a 404, which is reported as unsupported
+/// rather than as an error.
+fn parse_spaces_response(status: reqwest::StatusCode, body: &str) -> Result<SpacesList, String> {
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Ok(SpacesList { spaces: Vec::new(), supported: false });
+    }
+
+    if !status.is_success() {
+        return Err(format!("Failed to fetch spaces. Status: {}", status));
+    }
+
+    #[derive(Deserialize)]
+    struct SpacesResponse {
+        spaces: Vec<Space>,
+    }
+
+    let response: SpacesResponse = serde_json::from_str(body)
+        .map_err(|e| format!("Failed to deserialize spaces response: {}", e))?;
+
+    Ok(SpacesList { spaces: response.spaces, supported: true })
+}
+
+/// Returns a JPEG path for the given screenshot, transcoding PNGs into the temp directory
+/// since the upload backend only accepts JPEG images.
+fn prepare_screenshot_for_upload(file_path: &str) -> Result<String, String> {
+    let format = image::io::Reader::open(file_path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|e| format!("Failed to read screenshot: {}", e))?
+        .format();
+
+    match format {
+        Some(image::ImageFormat::Jpeg) => Ok(file_path.to_string()),
+        Some(image::ImageFormat::Png) => {
+            let file_stem = Path::new(file_path)
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or("Invalid file path")?;
+            let jpeg_path = std::env::temp_dir().join(format!("{}.jpg", file_stem));
+
+            let image = image::open(file_path).map_err(|e| format!("Failed to decode PNG screenshot: {}", e))?;
+            let mut output_file = File::create(&jpeg_path).map_err(|e| format!("Failed to create JPEG screenshot: {}", e))?;
+            if let Err(e) = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut output_file, 90)
+                .encode_image(&image.to_rgb8())
+            {
+                drop(output_file);
+                let _ = std::fs::remove_file(&jpeg_path);
+                return Err(format!("Failed to encode JPEG screenshot: {}", e));
+            }
+
+            println!("Transcoded PNG screenshot to {:?}", jpeg_path);
+
+            jpeg_path.to_str()
+                .map(|s| s.to_owned())
+                .ok_or_else(|| "Failed to convert JPEG screenshot path to string".to_string())
+        },
+        _ => Err(format!("Unsupported screenshot format for {}, expected PNG or JPEG", file_path)),
+    }
+}
+
 pub fn get_video_duration(file_path: &str) -> Result<f64, std::io::Error> {
     let ffmpeg_binary_path_str = ffmpeg_path_as_str().unwrap().to_owned();
 
@@ -202,4 +437,58 @@ fn log_video_info(file_path: &str) -> Result<(String, String, String, String, St
     let bit_rate: String = info_parts[4].to_string();
 
     Ok((codec_name, width, height, frame_rate, bit_rate))
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timed_cache_returns_value_until_ttl() {
+        let start = Instant::now();
+        let mut cache = TimedCache::new(Duration::from_secs(60));
+        cache.insert("token".to_string(), 1, start);
+
+        assert_eq!(cache.get("token", start + Duration::from_secs(59)), Some(1));
+        assert_eq!(cache.get("token", start + Duration::from_secs(60)), None);
+    }
+
+    #[test]
+    fn timed_cache_is_scoped_to_the_token() {
+        let start = Instant::now();
+        let mut cache = TimedCache::new(Duration::from_secs(60));
+        cache.insert("token".to_string(), 1, start);
+
+        assert_eq!(cache.get("other", start), None);
+
+        cache.insert("other".to_string(), 2, start);
+        assert_eq!(cache.get("token", start), None);
+        assert_eq!(cache.get("other", start), Some(2));
+    }
+
+    #[test]
+    fn spaces_not_found_is_unsupported() {
+        let spaces = parse_spaces_response(reqwest::StatusCode::NOT_FOUND, "<html>Not Found</html>");
+
+        assert_eq!(spaces, Ok(SpacesList { spaces: Vec::new(), supported: false }));
+    }
+
+    #[test]
+    fn spaces_response_is_parsed() {
+        let spaces = parse_spaces_response(reqwest::StatusCode::OK, r#"{"spaces":[{"id":"abc","name":"Design"}]}"#);
+
+        assert_eq!(
+            spaces,
+            Ok(SpacesList {
+                spaces: vec![Space { id: "abc".to_string(), name: "Design".to_string() }],
+                supported: true,
+            })
+        );
+    }
+
+    #[test]
+    fn spaces_errors_are_not_treated_as_unsupported() {
+        assert!(parse_spaces_response(reqwest::StatusCode::INTERNAL_SERVER_ERROR, "").is_err());
+        assert!(parse_spaces_response(reqwest::StatusCode::UNAUTHORIZED, "").is_err());
+        assert!(parse_spaces_response(reqwest::StatusCode::OK, "not json").is_err());
+    }
+}
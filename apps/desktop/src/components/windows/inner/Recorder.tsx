@@ -48,11 +48,258 @@ export const Recorder = () => {
   const proCheckPromise = isUserPro();
   const [proCheck, setProCheck] = useState<boolean>(false);
   const [limitReached, setLimitReached] = useState(false);
+  const [quotaWarning, setQuotaWarning] = useState(false);
+  const [spaces, setSpaces] = useState<{ id: string; name: string }[]>([]);
+  const [selectedSpaceId, setSelectedSpaceId] = useState("");
+  const [inputLatency, setInputLatency] = useState("");
+  const [micMuted, setMicMuted] = useState(false);
+  const [audioLevelDb, setAudioLevelDb] = useState(-96);
+  const [timelapseInterval, setTimelapseInterval] = useState("");
+  const [timelapseAvailable, setTimelapseAvailable] = useState(false);
+  const [creatingTimelapse, setCreatingTimelapse] = useState(false);
+  const [primaryDisplay, setPrimaryDisplay] = useState<{
+    name: string;
+    width: number;
+    height: number;
+  } | null>(null);
 
   useEffect(() => {
     proCheckPromise.then((result) => setProCheck(Boolean(result)));
   }, [proCheckPromise]);
 
+  useEffect(() => {
+    const session = JSON.parse(localStorage.getItem("session"));
+    const token = session?.token;
+    if (!token) {
+      return;
+    }
+
+    invoke("check_upload_quota", { token })
+      .then((quota: { usedBytes: number; totalBytes: number | null }) => {
+        if (quota.totalBytes && quota.usedBytes / quota.totalBytes > 0.8) {
+          setQuotaWarning(true);
+        }
+      })
+      .catch((error) => {
+        console.error("Error checking upload quota:", error);
+      });
+
+    setSelectedSpaceId(localStorage.getItem("defaultSpaceId") || "");
+    invoke("list_spaces", { token })
+      .then(
+        (result: {
+          spaces: { id: string; name: string }[];
+          supported: boolean;
+        }) => {
+          setSpaces(result.supported ? result.spaces : []);
+        }
+      )
+      .catch((error) => {
+        console.error("Error listing spaces:", error);
+      });
+  }, []);
+
+  useEffect(() => {
+    if (!selectedAudioDevice) {
+      setInputLatency("");
+      return;
+    }
+
+    const inputLatencies = JSON.parse(
+      localStorage.getItem("audioInputLatencies") || "{}"
+    );
+    setInputLatency(
+      inputLatencies[selectedAudioDevice.label] !== undefined
+        ? String(inputLatencies[selectedAudioDevice.label])
+        : ""
+    );
+  }, [selectedAudioDevice]);
+
+  const handleInputLatencyChange = (value: string) => {
+    setInputLatency(value);
+    if (!selectedAudioDevice) {
+      return;
+    }
+
+    const inputLatencies = JSON.parse(
+      localStorage.getItem("audioInputLatencies") || "{}"
+    );
+    const latency = parseInt(value, 10);
+    if (Number.isNaN(latency)) {
+      delete inputLatencies[selectedAudioDevice.label];
+    } else {
+      inputLatencies[selectedAudioDevice.label] = Math.max(
+        -500,
+        Math.min(500, latency)
+      );
+    }
+    localStorage.setItem("audioInputLatencies", JSON.stringify(inputLatencies));
+  };
+
+  useEffect(() => {
+    let unlistenFn: UnlistenFn | null = null;
+
+    const setupListener = async () => {
+      unlistenFn = await listen<{ muted: boolean }>(
+        "mic-mute-changed",
+        (event) => {
+          setMicMuted(event.payload.muted);
+        }
+      );
+    };
+
+    setupListener();
+
+    return () => {
+      if (unlistenFn) {
+        unlistenFn();
+      }
+    };
+  }, []);
+
+  useEffect(() => {
+    if (!isRecording) {
+      setMicMuted(false);
+      setAudioLevelDb(-96);
+    }
+  }, [isRecording]);
+
+  useEffect(() => {
+    let unlistenFn: UnlistenFn | null = null;
+
+    const setupListener = async () => {
+      unlistenFn = await listen<{ segmentTime: number; levelDb: number }>(
+        "audio-level-change",
+        (event) => {
+          setAudioLevelDb(event.payload.levelDb);
+        }
+      );
+    };
+
+    setupListener();
+
+    return () => {
+      if (unlistenFn) {
+        unlistenFn();
+      }
+    };
+  }, []);
+
+  useEffect(() => {
+    let unlistenFn: UnlistenFn | null = null;
+
+    const refreshDisplays = () => {
+      invoke("get_active_displays")
+        .then(
+          (
+            displays: {
+              name: string;
+              width: number;
+              height: number;
+              isPrimary: boolean;
+            }[]
+          ) => {
+            setPrimaryDisplay(
+              displays.find((display) => display.isPrimary) ??
+                displays[0] ??
+                null
+            );
+          }
+        )
+        .catch((error) => {
+          console.error("Error getting active displays:", error);
+        });
+    };
+
+    const setupListener = async () => {
+      unlistenFn = await listen("displays-changed", () => {
+        refreshDisplays();
+      });
+    };
+
+    refreshDisplays();
+    setupListener();
+
+    return () => {
+      if (unlistenFn) {
+        unlistenFn();
+      }
+    };
+  }, []);
+
+  useEffect(() => {
+    if (isRecording && primaryDisplay) {
+      toast(
+        `Display setup changed - recording ${primaryDisplay.width}x${primaryDisplay.height}`
+      );
+    }
+  }, [primaryDisplay]);
+
+  useEffect(() => {
+    let unlistenSuspected: UnlistenFn | null = null;
+    let unlistenCleared: UnlistenFn | null = null;
+
+    const setupListeners = async () => {
+      unlistenSuspected = await listen("protected-content-suspected", () => {
+        toast.error(
+          "Your recording has been black for a while - protected content (e.g. DRM video) can't be captured",
+          { id: "protected-content", duration: Infinity }
+        );
+      });
+      unlistenCleared = await listen("protected-content-cleared", () => {
+        toast.dismiss("protected-content");
+      });
+    };
+
+    setupListeners();
+
+    return () => {
+      if (unlistenSuspected) {
+        unlistenSuspected();
+      }
+      if (unlistenCleared) {
+        unlistenCleared();
+      }
+    };
+  }, []);
+
+  const handleToggleMicMuted = async () => {
+    await invoke("set_mic_muted", { muted: !micMuted }).catch((error) => {
+      console.error("Error toggling microphone mute:", error);
+    });
+  };
+
+  const handleCopySystemInfo = async () => {
+    try {
+      await invoke("copy_system_info_to_clipboard");
+      toast.success("System info copied to clipboard");
+    } catch (error) {
+      console.error("Error copying system info:", error);
+      toast.error("Failed to copy system info");
+    }
+  };
+
+  const handleCreateTimelapse = async () => {
+    setCreatingTimelapse(true);
+    try {
+      const path: string = await invoke("create_timelapse_gif");
+      toast.success(`Timelapse saved to ${path}`);
+    } catch (error) {
+      console.error("Error creating timelapse:", error);
+      toast.error("Failed to create timelapse");
+    }
+    setCreatingTimelapse(false);
+  };
+
+  const handleSpaceChange = (spaceId: string) => {
+    setSelectedSpaceId(spaceId);
+    if (spaceId) {
+      localStorage.setItem("defaultSpaceId", spaceId);
+    } else {
+      localStorage.removeItem("defaultSpaceId");
+    }
+  };
+
   const handleContextClick = async (option: "video" | "audio") => {
     const { showMenu } = await import("tauri-plugin-context-menu");
     const deviceKind = option === "video" ? "videoinput" : "audioinput";
@@ -110,8 +357,12 @@ export const Recorder = () => {
   const prepareVideoData = async () => {
     const session = JSON.parse(localStorage.getItem("session"));
     const token = session?.token;
+    const spaceParam =
+      selectedSpaceId && spaces.some((space) => space.id === selectedSpaceId)
+        ? `&spaceId=${encodeURIComponent(selectedSpaceId)}`
+        : "";
     const res = await authFetch(
-      `${process.env.NEXT_PUBLIC_URL}/api/desktop/video/create?origin=${window.location.origin}`,
+      `${process.env.NEXT_PUBLIC_URL}/api/desktop/video/create?origin=${window.location.origin}${spaceParam}`,
       {
         method: "GET",
         credentials: "include",
@@ -202,6 +453,7 @@ export const Recorder = () => {
       });
     });
     await emit("toggle-recording", true);
+    const latency = parseInt(inputLatency, 10);
     try {
       await invoke("start_dual_recording", {
         options: {
@@ -212,10 +464,23 @@ export const Recorder = () => {
           aws_bucket: videoData.aws_bucket,
           screen_index: "Capture screen 0",
           video_index: String(selectedVideoDevice?.index),
+          input_latency_ms:
+            selectedAudioDevice && !Number.isNaN(latency) ? latency : null,
         },
       }).catch((error) => {
         console.error("Error invoking start_screen_recording:", error);
       });
+
+      const intervalSecs = parseInt(timelapseInterval, 10);
+      if (intervalSecs > 0) {
+        await invoke("start_interval_screenshots", { intervalSecs })
+          .then(() => setTimelapseAvailable(true))
+          .catch((error) => {
+            console.error("Error starting interval screenshots:", error);
+          });
+      } else {
+        setTimelapseAvailable(false);
+      }
     } catch (error) {
       console.error("Error starting screen recording:", error);
       setStartingRecording(false);
@@ -413,7 +678,11 @@ export const Recorder = () => {
                       }
                     }}
                     icon={<Screen className="w-5 h-5" />}
-                    label="Full screen"
+                    label={
+                      primaryDisplay
+                        ? `${primaryDisplay.width}x${primaryDisplay.height}`
+                        : "Full screen"
+                    }
                     active={selectedDisplayType === "screen"}
                   />
                   <ActionButton
@@ -462,6 +731,90 @@ export const Recorder = () => {
               </div>
             </div>
           </div>
+          {selectedAudioDevice && (
+            <label className="w-full mb-3 flex items-center justify-between text-sm text-gray-600">
+              Mic delay (ms)
+              <input
+                type="number"
+                min={-500}
+                max={500}
+                step={10}
+                placeholder="0"
+                className="w-20 p-1 text-sm border border-gray-200 rounded-lg bg-white"
+                value={inputLatency}
+                disabled={isRecording || startingRecording}
+                onChange={(event) =>
+                  handleInputLatencyChange(event.target.value)
+                }
+              />
+            </label>
+          )}
+          <label className="w-full mb-3 flex items-center justify-between text-sm text-gray-600">
+            Timelapse every (s)
+            <input
+              type="number"
+              min={0}
+              step={1}
+              placeholder="Off"
+              className="w-20 p-1 text-sm border border-gray-200 rounded-lg bg-white"
+              value={timelapseInterval}
+              disabled={isRecording || startingRecording}
+              onChange={(event) => setTimelapseInterval(event.target.value)}
+            />
+          </label>
+          {spaces.length > 0 && (
+            <select
+              className="w-full mb-3 p-2 text-sm border border-gray-200 rounded-lg bg-white"
+              value={selectedSpaceId}
+              disabled={isRecording || startingRecording}
+              onChange={(event) => handleSpaceChange(event.target.value)}
+            >
+              <option value="">No space</option>
+              {spaces.map((space) => (
+                <option key={space.id} value={space.id}>
+                  {space.name}
+                </option>
+              ))}
+            </select>
+          )}
+          {!isRecording && timelapseAvailable && (
+            <Button
+              variant="outline"
+              size="sm"
+              className="w-full flex mx-auto mb-3"
+              onClick={handleCreateTimelapse}
+              spinner={creatingTimelapse}
+            >
+              Create Timelapse
+            </Button>
+          )}
+          {isRecording && selectedAudioDevice && (
+            <div className="flex items-center mb-3 space-x-2">
+              <Button
+                variant="outline"
+                size="sm"
+                className="flex-1 flex"
+                onClick={handleToggleMicMuted}
+                disabled={stoppingRecording}
+                title="Cmd/Ctrl+Shift+M"
+              >
+                {micMuted ? "Unmute Microphone" : "Mute Microphone"}
+              </Button>
+              <div className="w-16 h-2 bg-gray-200 rounded-full overflow-hidden">
+                <div
+                  className="h-full bg-green-500 transition-all duration-100"
+                  style={{
+                    // Map the -60dB..0dB range onto the meter, anything quieter reads as silence
+                    width: `${
+                      micMuted
+                        ? 0
+                        : Math.max(0, Math.min(100, ((audioLevelDb + 60) / 60) * 100))
+                    }%`,
+                  }}
+                />
+              </div>
+            </div>
+          )}
           <Button
             {...(isRecording && { variant: "destructive" })}
             className="w-full flex mx-auto"
@@ -488,6 +841,17 @@ export const Recorder = () => {
             ) : (
               <p className="text-sm text-gray-600">No recording limit</p>
             )}
+            {quotaWarning && (
+              <p className="text-sm text-red-600">
+                You have used over 80% of your storage
+              </p>
+            )}
+            <button
+              className="mt-1 text-xs text-gray-500 hover:text-gray-700 underline"
+              onClick={handleCopySystemInfo}
+            >
+              Copy System Info
+            </button>
           </div>
         </div>
         <Toaster />
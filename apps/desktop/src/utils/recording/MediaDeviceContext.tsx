@@ -203,7 +203,63 @@ export const MediaDeviceProvider: React.FC<React.PropsWithChildren<{}>> = ({
         unlistenFnTraySetDevice();
       }
     };
-  }, [selectedVideoDevice, selectedAudioDevice]);
+  }, [devices, selectedVideoDevice, selectedAudioDevice]);
+
+  useEffect(() => {
+    let unlistenFnAudioDevicesChanged: any;
+
+    const setupListener = async () => {
+      try {
+        unlistenFnAudioDevicesChanged = await listen<{ devices: string[] }>(
+          "audio-devices-changed",
+          (event) => {
+            const audioDevices = event.payload.devices.map(
+              (device, index) => ({
+                index: index,
+                label: device,
+                kind: "audioinput",
+                id: device,
+              })
+            ) as Device[];
+
+            window.localStorage.setItem(
+              "audioDevices",
+              JSON.stringify(
+                audioDevices.map(({ id, label, kind }) => ({ id, label, kind }))
+              )
+            );
+            setDevices((devices) => [
+              ...devices.filter((device) => device.kind !== "audioinput"),
+              ...audioDevices,
+            ]);
+
+            if (selectedAudioDevice) {
+              const stillConnected = audioDevices.find(
+                (device) => device.id === selectedAudioDevice.id
+              );
+              // Indexes shift as devices come and go, so keep the selection pointing at
+              // the refreshed entry, or drop it if the microphone went away while idle
+              if (stillConnected) {
+                setSelectedAudioDevice(stillConnected);
+              } else if (!isRecording) {
+                setSelectedAudioDevice(null);
+              }
+            }
+          }
+        );
+      } catch (error) {
+        console.error("Error setting up audio-devices-changed listener:", error);
+      }
+    };
+
+    setupListener();
+
+    return () => {
+      if (unlistenFnAudioDevicesChanged) {
+        unlistenFnAudioDevicesChanged();
+      }
+    };
+  }, [selectedAudioDevice, isRecording]);
 
   return (
     <MediaDeviceContext.Provider
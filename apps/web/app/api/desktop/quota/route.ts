@@ -0,0 +1,121 @@
+import { type NextRequest } from "next/server";
+import { getCurrentUser } from "@cap/database/auth/session";
+import { cookies } from "next/headers";
+import { isUserOnProPlan } from "@cap/utils";
+import { ListObjectsV2Command, S3Client } from "@aws-sdk/client-s3";
+
+const allowedOrigins = [
+  process.env.NEXT_PUBLIC_URL,
+  "http://localhost:3001",
+  "tauri://localhost",
+  "http://tauri.localhost",
+  "https://tauri.localhost",
+];
+
+export async function OPTIONS(req: NextRequest) {
+  const params = req.nextUrl.searchParams;
+  const origin = params.get("origin") || null;
+  const originalOrigin = req.nextUrl.origin;
+
+  return new Response(null, {
+    status: 200,
+    headers: {
+      "Access-Control-Allow-Origin":
+        origin && allowedOrigins.includes(origin)
+          ? origin
+          : allowedOrigins.includes(originalOrigin)
+          ? originalOrigin
+          : "null",
+      "Access-Control-Allow-Credentials": "true",
+      "Access-Control-Allow-Methods": "GET, OPTIONS",
+      "Access-Control-Allow-Headers": "Authorization, sentry-trace, baggage",
+    },
+  });
+}
+
+export async function GET(req: NextRequest) {
+  const token = req.headers.get("authorization")?.split(" ")[1];
+  if (token) {
+    cookies().set({
+      name: "next-auth.session-token",
+      value: token,
+      path: "/",
+      sameSite: "none",
+      secure: true,
+      httpOnly: true,
+    });
+  }
+  const params = req.nextUrl.searchParams;
+  const origin = params.get("origin") || null;
+  const originalOrigin = req.nextUrl.origin;
+  const user = await getCurrentUser();
+
+  if (!user) {
+    return new Response(JSON.stringify({ error: true }), {
+      status: 401,
+      headers: {
+        "Content-Type": "application/json",
+      },
+    });
+  }
+
+  const isSubscribed = isUserOnProPlan({
+    subscriptionStatus: user.stripeSubscriptionStatus as string,
+  });
+
+  const s3Client = new S3Client({
+    region: process.env.CAP_AWS_REGION || "",
+    credentials: {
+      accessKeyId: process.env.CAP_AWS_ACCESS_KEY || "",
+      secretAccessKey: process.env.CAP_AWS_SECRET_KEY || "",
+    },
+  });
+
+  let usedBytes = 0;
+  let continuationToken: string | undefined = undefined;
+
+  do {
+    const listedObjects = await s3Client.send(
+      new ListObjectsV2Command({
+        Bucket: process.env.CAP_AWS_BUCKET || "",
+        Prefix: `${user.id}/`,
+        ContinuationToken: continuationToken,
+      })
+    );
+
+    for (const content of listedObjects.Contents ?? []) {
+      usedBytes += content.Size ?? 0;
+    }
+
+    continuationToken = listedObjects.IsTruncated
+      ? listedObjects.NextContinuationToken
+      : undefined;
+  } while (continuationToken);
+
+  // Storage is unlimited unless a free plan limit is configured for this deployment
+  const freeStorageLimit = Number(process.env.CAP_FREE_STORAGE_LIMIT_BYTES);
+  const totalBytes =
+    !isSubscribed && freeStorageLimit > 0 ? freeStorageLimit : null;
+
+  return new Response(
+    JSON.stringify({
+      usedBytes,
+      totalBytes,
+      plan: isSubscribed ? "pro" : "free",
+    }),
+    {
+      status: 200,
+      headers: {
+        "Access-Control-Allow-Origin":
+          origin && allowedOrigins.includes(origin)
+            ? origin
+            : allowedOrigins.includes(originalOrigin)
+            ? originalOrigin
+            : "null",
+        "Access-Control-Allow-Credentials": "true",
+        "Access-Control-Allow-Methods": "GET, OPTIONS",
+        "Access-Control-Allow-Headers": "Authorization, sentry-trace, baggage",
+      },
+    }
+  );
+}
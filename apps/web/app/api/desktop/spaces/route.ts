@@ -0,0 +1,91 @@
+import { type NextRequest } from "next/server";
+import { db } from "@cap/database";
+import { spaceMembers, spaces } from "@cap/database/schema";
+import { getCurrentUser } from "@cap/database/auth/session";
+import { eq, or } from "drizzle-orm";
+import { cookies } from "next/headers";
+
+const allowedOrigins = [
+  process.env.NEXT_PUBLIC_URL,
+  "http://localhost:3001",
+  "tauri://localhost",
+  "http://tauri.localhost",
+  "https://tauri.localhost",
+];
+
+export async function OPTIONS(req: NextRequest) {
+  const params = req.nextUrl.searchParams;
+  const origin = params.get("origin") || null;
+  const originalOrigin = req.nextUrl.origin;
+
+  return new Response(null, {
+    status: 200,
+    headers: {
+      "Access-Control-Allow-Origin":
+        origin && allowedOrigins.includes(origin)
+          ? origin
+          : allowedOrigins.includes(originalOrigin)
+          ? originalOrigin
+          : "null",
+      "Access-Control-Allow-Credentials": "true",
+      "Access-Control-Allow-Methods": "GET, OPTIONS",
+      "Access-Control-Allow-Headers": "Authorization, sentry-trace, baggage",
+    },
+  });
+}
+
+export async function GET(req: NextRequest) {
+  const token = req.headers.get("authorization")?.split(" ")[1];
+  if (token) {
+    cookies().set({
+      name: "next-auth.session-token",
+      value: token,
+      path: "/",
+      sameSite: "none",
+      secure: true,
+      httpOnly: true,
+    });
+  }
+
+  const user = await getCurrentUser();
+  const params = req.nextUrl.searchParams;
+  const origin = params.get("origin") || null;
+  const originalOrigin = req.nextUrl.origin;
+  const headers = {
+    "Access-Control-Allow-Origin":
+      origin && allowedOrigins.includes(origin)
+        ? origin
+        : allowedOrigins.includes(originalOrigin)
+        ? originalOrigin
+        : "null",
+    "Access-Control-Allow-Credentials": "true",
+    "Access-Control-Allow-Methods": "GET, OPTIONS",
+    "Access-Control-Allow-Headers": "Authorization, sentry-trace, baggage",
+  };
+
+  if (!user) {
+    return new Response(JSON.stringify({ error: true }), {
+      status: 401,
+      headers,
+    });
+  }
+
+  const rows = await db
+    .select({
+      id: spaces.id,
+      name: spaces.name,
+    })
+    .from(spaces)
+    .where(or(eq(spaces.ownerId, user.id), eq(spaceMembers.userId, user.id)))
+    .leftJoin(spaceMembers, eq(spaces.id, spaceMembers.spaceId));
+
+  // The member join returns one row per membership, so a space can appear more than once
+  const userSpaces = rows.filter(
+    (space, index) => rows.findIndex((row) => row.id === space.id) === index
+  );
+
+  return new Response(JSON.stringify({ spaces: userSpaces }), {
+    status: 200,
+    headers,
+  });
+}
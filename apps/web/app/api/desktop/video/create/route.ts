@@ -1,8 +1,14 @@
 import { type NextRequest } from "next/server";
 import { db } from "@cap/database";
-import { videos } from "@cap/database/schema";
+import {
+  sharedVideos,
+  spaceMembers,
+  spaces,
+  videos,
+} from "@cap/database/schema";
 import { getCurrentUser } from "@cap/database/auth/session";
 import { nanoId } from "@cap/database/helpers";
+import { and, eq, or } from "drizzle-orm";
 import { cookies } from "next/headers";
 
 const allowedOrigins = [
@@ -52,6 +58,7 @@ export async function GET(req: NextRequest) {
   const awsBucket = process.env.CAP_AWS_BUCKET;
   const params = req.nextUrl.searchParams;
   const origin = params.get("origin") || null;
+  const spaceId = params.get("spaceId") || null;
   const originalOrigin = req.nextUrl.origin;
 
   console.log("cookies:", cookies().getAll());
@@ -87,6 +94,31 @@ export async function GET(req: NextRequest) {
     awsBucket: awsBucket,
   });
 
+  let sharedSpaceId: string | null = null;
+
+  if (spaceId) {
+    const [space] = await db
+      .select({ id: spaces.id })
+      .from(spaces)
+      .where(
+        and(
+          eq(spaces.id, spaceId),
+          or(eq(spaces.ownerId, user.id), eq(spaceMembers.userId, user.id))
+        )
+      )
+      .leftJoin(spaceMembers, eq(spaces.id, spaceMembers.spaceId));
+
+    if (space) {
+      await db.insert(sharedVideos).values({
+        id: nanoId(),
+        videoId: id,
+        spaceId: space.id,
+        sharedByUserId: user.id,
+      });
+      sharedSpaceId = space.id;
+    }
+  }
+
   if (
     process.env.NEXT_PUBLIC_IS_CAP &&
     process.env.NEXT_PUBLIC_ENVIRONMENT === "production"
@@ -116,6 +148,7 @@ export async function GET(req: NextRequest) {
       user_id: user.id,
       aws_region: awsRegion,
       aws_bucket: awsBucket,
+      space_id: sharedSpaceId,
     }),
     {
       status: 200,